@@ -1,8 +1,55 @@
+use std::cell::Cell;
+use std::env;
 use std::fmt::{self, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
 
+use rand::Rng;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
+/// Whether `Display` impls below should emit ANSI SGR escapes for styling.
+///
+/// Set once at startup via `set_color_enabled`, based on a CLI flag or a TTY check.
+static COLOR_ENABLED: AtomicBool = AtomicBool::new(false);
+
+thread_local! {
+    /// Overrides `color_enabled()` to `false` for the current thread, for the
+    /// duration of a `render_plain` call. A thread-local (rather than
+    /// flipping `COLOR_ENABLED` itself) means it can't race with another
+    /// thread reading the global setting for its own, unrelated render.
+    static FORCE_PLAIN: Cell<bool> = Cell::new(false);
+}
+
+pub fn set_color_enabled(enabled: bool) {
+    COLOR_ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+fn color_enabled() -> bool {
+    if FORCE_PLAIN.with(Cell::get) {
+        return false;
+    }
+    COLOR_ENABLED.load(Ordering::Relaxed)
+}
+
+/// Renders `component` as plain text, regardless of the global color
+/// setting: no SGR escapes, no OSC 8 hyperlinks, and no hover-event
+/// footnotes (which would otherwise splice a literal newline into the
+/// output). Consumers like the IRC gateway need this, since a single
+/// PRIVMSG line can't survive any of that.
+pub fn render_plain(component: &Component) -> String {
+    FORCE_PLAIN.with(|f| f.set(true));
+    let rendered = component.to_string();
+    FORCE_PLAIN.with(|f| f.set(false));
+    rendered
+}
+
+fn truecolor_supported() -> bool {
+    match env::var("COLORTERM") {
+        Ok(v) => v == "truecolor" || v == "24bit",
+        Err(_) => false,
+    }
+}
+
 #[derive(Serialize, Deserialize, PartialEq, Debug)]
 #[serde(untagged)]
 pub enum Component {
@@ -15,9 +62,22 @@ pub enum Component {
 
 impl fmt::Display for Component {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut footnotes = Vec::new();
+        self.fmt_styled(f, Style::default(), &mut footnotes)?;
+        write_footnotes(f, &footnotes)
+    }
+}
+
+impl Component {
+    fn fmt_styled(
+        &self,
+        f: &mut fmt::Formatter,
+        style: Style,
+        footnotes: &mut Vec<String>,
+    ) -> fmt::Result {
         match self {
-            Component::String(v) => v.fmt(f),
-            Component::Translation(v) => v.fmt(f),
+            Component::String(v) => v.fmt_styled(f, style, footnotes),
+            Component::Translation(v) => v.fmt_styled(f, style, footnotes),
             Component::Keybind(v) => fmt::Debug::fmt(v, f),
             Component::Score(v) => fmt::Debug::fmt(v, f),
             Component::Selector(v) => fmt::Debug::fmt(v, f),
@@ -38,15 +98,35 @@ pub enum StringComponent {
 
 impl fmt::Display for StringComponent {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut footnotes = Vec::new();
+        self.fmt_styled(f, Style::default(), &mut footnotes)?;
+        write_footnotes(f, &footnotes)
+    }
+}
+
+impl StringComponent {
+    fn fmt_styled(
+        &self,
+        f: &mut fmt::Formatter,
+        style: Style,
+        footnotes: &mut Vec<String>,
+    ) -> fmt::Result {
         match self {
-            StringComponent::Raw(text) => f.write_str(text),
+            StringComponent::Raw(text) => write_text(f, text, style),
             StringComponent::Mixed { text, fields } => {
-                f.write_str(&text)?;
+                let own_style = style.merge(fields);
+                if color_enabled() {
+                    own_style.write_sgr(f)?;
+                }
+                write_text_with_events(f, text, own_style, fields, footnotes)?;
                 if let Some(extra) = &fields.extra {
                     for extra in extra {
-                        extra.fmt(f)?;
+                        extra.fmt_styled(f, own_style, footnotes)?;
                     }
                 }
+                if color_enabled() {
+                    style.write_sgr(f)?;
+                }
                 Ok(())
             }
         }
@@ -63,16 +143,42 @@ pub struct TranslationComponent {
 
 impl fmt::Display for TranslationComponent {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "[{}]", self.translate)?;
+        let mut footnotes = Vec::new();
+        self.fmt_styled(f, Style::default(), &mut footnotes)?;
+        write_footnotes(f, &footnotes)
+    }
+}
+
+impl TranslationComponent {
+    fn fmt_styled(
+        &self,
+        f: &mut fmt::Formatter,
+        style: Style,
+        footnotes: &mut Vec<String>,
+    ) -> fmt::Result {
+        let own_style = style.merge(&self.fields);
+        if color_enabled() {
+            own_style.write_sgr(f)?;
+        }
+        write_text_with_events(
+            f,
+            &format!("[{}]", self.translate),
+            own_style,
+            &self.fields,
+            footnotes,
+        )?;
         for component in &self.with {
             f.write_char(' ')?;
-            component.fmt(f)?;
+            component.fmt_styled(f, own_style, footnotes)?;
         }
         if let Some(extra) = &self.fields.extra {
             for extra in extra {
-                extra.fmt(f)?;
+                extra.fmt_styled(f, own_style, footnotes)?;
             }
         }
+        if color_enabled() {
+            style.write_sgr(f)?;
+        }
         Ok(())
     }
 }
@@ -133,6 +239,204 @@ pub enum HoverEvent {
     ShowAchievement(Box<StringComponent>),
 }
 
+/// A resolved ANSI foreground color: 24-bit RGB plus a classic 16-color fallback.
+#[derive(Clone, Copy, PartialEq, Debug)]
+struct AnsiColor {
+    rgb: (u8, u8, u8),
+    classic: u8,
+}
+
+/// The accumulated style at some point in the component tree, used to emit SGR
+/// escapes and to restore the parent's style once a child finishes rendering.
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+struct Style {
+    color: Option<AnsiColor>,
+    bold: bool,
+    italic: bool,
+    underlined: bool,
+    strikethrough: bool,
+    obfuscated: bool,
+}
+
+impl Style {
+    /// Merges `fields` on top of `self`, falling back to the parent's value for
+    /// anything the child leaves unset.
+    fn merge(self, fields: &ComponentFields) -> Style {
+        Style {
+            color: fields
+                .color
+                .as_ref()
+                .and_then(|c| parse_color(c))
+                .or(self.color),
+            bold: fields.bold.unwrap_or(self.bold),
+            italic: fields.italic.unwrap_or(self.italic),
+            underlined: fields.underlined.unwrap_or(self.underlined),
+            strikethrough: fields.strikethrough.unwrap_or(self.strikethrough),
+            obfuscated: fields.obfuscated.unwrap_or(self.obfuscated),
+        }
+    }
+
+    fn write_sgr(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut codes = Vec::new();
+        if self.bold {
+            codes.push("1".to_owned());
+        }
+        if self.italic {
+            codes.push("3".to_owned());
+        }
+        if self.underlined {
+            codes.push("4".to_owned());
+        }
+        if self.strikethrough {
+            codes.push("9".to_owned());
+        }
+        if let Some(color) = self.color {
+            if truecolor_supported() {
+                codes.push(format!("38;2;{};{};{}", color.rgb.0, color.rgb.1, color.rgb.2));
+            } else {
+                codes.push(color.classic.to_string());
+            }
+        }
+        // Reset before re-applying so the previous style never bleeds through,
+        // then restore exactly the style that was passed in, not a blanket reset.
+        write!(f, "\x1b[0;{}m", codes.join(";"))
+    }
+}
+
+const NAMED_COLORS: &[(&str, (u8, u8, u8), u8)] = &[
+    ("black", (0, 0, 0), 30),
+    ("dark_blue", (0, 0, 170), 34),
+    ("dark_green", (0, 170, 0), 32),
+    ("dark_aqua", (0, 170, 170), 36),
+    ("dark_red", (170, 0, 0), 31),
+    ("dark_purple", (170, 0, 170), 35),
+    ("gold", (255, 170, 0), 33),
+    ("gray", (170, 170, 170), 37),
+    ("dark_gray", (85, 85, 85), 90),
+    ("blue", (85, 85, 255), 94),
+    ("green", (85, 255, 85), 92),
+    ("aqua", (85, 255, 255), 96),
+    ("red", (255, 85, 85), 91),
+    ("light_purple", (255, 85, 255), 95),
+    ("yellow", (255, 255, 85), 93),
+    ("white", (255, 255, 255), 97),
+];
+
+fn parse_color(name: &str) -> Option<AnsiColor> {
+    if let Some(hex) = name.strip_prefix('#') {
+        if hex.len() != 6 {
+            return None;
+        }
+        let r = u8::from_str_radix(&hex[0..2], 16).ok()?;
+        let g = u8::from_str_radix(&hex[2..4], 16).ok()?;
+        let b = u8::from_str_radix(&hex[4..6], 16).ok()?;
+        return Some(AnsiColor {
+            rgb: (r, g, b),
+            classic: nearest_classic(r, g, b),
+        });
+    }
+    NAMED_COLORS
+        .iter()
+        .find(|(n, _, _)| *n == name)
+        .map(|(_, rgb, classic)| AnsiColor {
+            rgb: *rgb,
+            classic: *classic,
+        })
+}
+
+fn nearest_classic(r: u8, g: u8, b: u8) -> u8 {
+    NAMED_COLORS
+        .iter()
+        .min_by_key(|(_, (pr, pg, pb), _)| {
+            let dr = i32::from(*pr) - i32::from(r);
+            let dg = i32::from(*pg) - i32::from(g);
+            let db = i32::from(*pb) - i32::from(b);
+            dr * dr + dg * dg + db * db
+        })
+        .map(|(_, _, classic)| *classic)
+        .unwrap_or(39)
+}
+
+const OBFUSCATE_CHARS: &[char] = &[
+    '!', '#', '$', '%', '&', '*', '0', '1', '2', '3', '4', '5', '6', '7', '8', '9', 'A', 'B', 'C',
+    'D', 'E', 'F', 'G', 'H', 'I', 'J', 'K', 'L', 'M', 'N', 'O', 'P', 'Q', 'R', 'S', 'T', 'U', 'V',
+    'W', 'X', 'Y', 'Z',
+];
+
+fn write_text(f: &mut fmt::Formatter, text: &str, style: Style) -> fmt::Result {
+    if style.obfuscated && color_enabled() {
+        let mut rng = rand::thread_rng();
+        for c in text.chars() {
+            if c.is_whitespace() {
+                f.write_char(c)?;
+            } else {
+                f.write_char(OBFUSCATE_CHARS[rng.gen_range(0, OBFUSCATE_CHARS.len())])?;
+            }
+        }
+        Ok(())
+    } else {
+        f.write_str(text)
+    }
+}
+
+/// Writes `text` styled as usual, additionally wrapping it in an OSC 8
+/// hyperlink for `ClickEvent::OpenUrl`, appending a dim `[command]` hint for
+/// `SuggestCommand`/`RunCommand`, and recording any `HoverEvent` tooltip as a
+/// numbered footnote (printed after the whole message by `write_footnotes`).
+/// Only done when rendering to a TTY; plain mode is unaffected.
+fn write_text_with_events(
+    f: &mut fmt::Formatter,
+    text: &str,
+    style: Style,
+    fields: &ComponentFields,
+    footnotes: &mut Vec<String>,
+) -> fmt::Result {
+    let tty = color_enabled();
+    let url = match (&fields.click_event, tty) {
+        (Some(ClickEvent::OpenUrl(url)), true) => Some(url.as_str()),
+        _ => None,
+    };
+    if let Some(url) = url {
+        write!(f, "\x1b]8;;{}\x1b\\", url)?;
+    }
+    write_text(f, text, style)?;
+    if url.is_some() {
+        write!(f, "\x1b]8;;\x1b\\")?;
+    }
+    if !tty {
+        return Ok(());
+    }
+    if let Some(cmd) = match &fields.click_event {
+        Some(ClickEvent::SuggestCommand(cmd)) | Some(ClickEvent::RunCommand(cmd)) => Some(cmd),
+        _ => None,
+    } {
+        write!(f, " \x1b[2m[{}]\x1b[0m", cmd)?;
+        style.write_sgr(f)?;
+    }
+    if let Some(hover) = &fields.hover_event {
+        footnotes.push(hover_text(hover));
+        write!(f, "\x1b[2m[{}]\x1b[0m", footnotes.len())?;
+        style.write_sgr(f)?;
+    }
+    Ok(())
+}
+
+fn hover_text(event: &HoverEvent) -> String {
+    match event {
+        HoverEvent::ShowText(v)
+        | HoverEvent::ShowItem(v)
+        | HoverEvent::ShowEntity(v)
+        | HoverEvent::ShowAchievement(v) => v.to_string(),
+    }
+}
+
+fn write_footnotes(f: &mut fmt::Formatter, footnotes: &[String]) -> fmt::Result {
+    for (i, note) in footnotes.iter().enumerate() {
+        write!(f, "\n  [{}] {}", i + 1, note)?;
+    }
+    Ok(())
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -203,4 +507,83 @@ mod test {
         let r: Component = serde_json::from_str(input).unwrap();
         assert_eq!(r, expect);
     }
+
+    #[test]
+    fn parse_color_named() {
+        let c = parse_color("gold").unwrap();
+        assert_eq!(c.rgb, (255, 170, 0));
+        assert_eq!(c.classic, 33);
+    }
+
+    #[test]
+    fn parse_color_hex() {
+        let c = parse_color("#1a2b3c").unwrap();
+        assert_eq!(c.rgb, (0x1a, 0x2b, 0x3c));
+    }
+
+    #[test]
+    fn parse_color_invalid() {
+        assert!(parse_color("not_a_color").is_none());
+        assert!(parse_color("#12345").is_none());
+        assert!(parse_color("#zzzzzz").is_none());
+    }
+
+    #[test]
+    fn nearest_classic_exact_match() {
+        assert_eq!(nearest_classic(255, 170, 0), 33); // gold
+    }
+
+    #[test]
+    fn nearest_classic_picks_closest() {
+        // Close to "red" (255, 85, 85) but not exact.
+        assert_eq!(nearest_classic(250, 90, 90), 91);
+    }
+
+    #[test]
+    fn style_merge_inherits_unset_fields() {
+        let parent = Style {
+            color: parse_color("red"),
+            bold: true,
+            ..Style::default()
+        };
+        let merged = parent.merge(&ComponentFields::default());
+        assert_eq!(merged, parent);
+    }
+
+    #[test]
+    fn style_merge_overrides_set_fields() {
+        let parent = Style {
+            color: parse_color("red"),
+            bold: true,
+            ..Style::default()
+        };
+        let fields = ComponentFields {
+            bold: Some(false),
+            color: Some("blue".into()),
+            ..ComponentFields::default()
+        };
+        let merged = parent.merge(&fields);
+        assert_eq!(merged.color, parse_color("blue"));
+        assert!(!merged.bold);
+        assert!(!merged.italic); // untouched field still falls back to the parent
+    }
+
+    #[test]
+    fn render_plain_strips_ansi_and_footnotes() {
+        let component = Component::String(StringComponent::Mixed {
+            text: "hi".into(),
+            fields: ComponentFields {
+                bold: Some(true),
+                color: Some("red".into()),
+                hover_event: Some(HoverEvent::ShowText(Box::new(StringComponent::Raw(
+                    "tooltip".into(),
+                )))),
+                ..ComponentFields::default()
+            },
+        });
+        let plain = render_plain(&component);
+        assert_eq!(plain, "hi");
+        assert!(!plain.contains('\x1b'));
+        assert!(!plain.contains('\n'));
+    }
 }
@@ -0,0 +1,135 @@
+//! SQLite-backed chat history, so messages survive restarts and reconnects.
+//!
+//! Mirrors an IRC CHATHISTORY-style query surface: callers can ask for the
+//! latest `N` messages, or for `N` messages strictly before a given
+//! timestamp (for paging further back). Both return newest-anchored but
+//! oldest-first, so printing them in order reads naturally.
+
+use chrono::{DateTime, Utc};
+use rusqlite::{params, Connection};
+
+pub struct HistoryEntry {
+    pub timestamp: DateTime<Utc>,
+    pub sender: Option<String>,
+    pub rendered: String,
+    pub raw: String,
+}
+
+pub struct History {
+    conn: Connection,
+}
+
+impl History {
+    pub fn open(path: &str) -> rusqlite::Result<History> {
+        Self::from_connection(Connection::open(path)?)
+    }
+
+    fn from_connection(conn: Connection) -> rusqlite::Result<History> {
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS chat_history (
+                id INTEGER PRIMARY KEY,
+                timestamp TEXT NOT NULL,
+                sender TEXT,
+                rendered TEXT NOT NULL,
+                raw TEXT NOT NULL
+            )",
+            [],
+        )?;
+        Ok(History { conn })
+    }
+
+    pub fn record(&self, entry: &HistoryEntry) -> rusqlite::Result<()> {
+        self.conn.execute(
+            "INSERT INTO chat_history (timestamp, sender, rendered, raw) VALUES (?1, ?2, ?3, ?4)",
+            params![
+                entry.timestamp.to_rfc3339(),
+                entry.sender,
+                entry.rendered,
+                entry.raw
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// The most recent `count` messages, oldest first.
+    pub fn latest(&self, count: usize) -> rusqlite::Result<Vec<HistoryEntry>> {
+        self.query(None, count)
+    }
+
+    /// Up to `count` messages strictly before `before`, oldest first.
+    pub fn before(&self, before: DateTime<Utc>, count: usize) -> rusqlite::Result<Vec<HistoryEntry>> {
+        self.query(Some(before), count)
+    }
+
+    /// Up to `count` messages, strictly before `before` if given, oldest first.
+    fn query(&self, before: Option<DateTime<Utc>>, count: usize) -> rusqlite::Result<Vec<HistoryEntry>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT timestamp, sender, rendered, raw FROM chat_history
+             WHERE ?1 IS NULL OR timestamp < ?1 ORDER BY timestamp DESC LIMIT ?2",
+        )?;
+        let mut entries: Vec<HistoryEntry> = stmt
+            .query_map(params![before.map(|b| b.to_rfc3339()), count as i64], |row| {
+                let timestamp: String = row.get(0)?;
+                Ok(HistoryEntry {
+                    timestamp: DateTime::parse_from_rfc3339(&timestamp)
+                        .expect("stored timestamp is not valid rfc3339")
+                        .with_timezone(&Utc),
+                    sender: row.get(1)?,
+                    rendered: row.get(2)?,
+                    raw: row.get(3)?,
+                })
+            })?
+            .collect::<rusqlite::Result<_>>()?;
+        entries.reverse();
+        Ok(entries)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use chrono::Duration;
+
+    fn entry(timestamp: DateTime<Utc>, rendered: &str) -> HistoryEntry {
+        HistoryEntry {
+            timestamp,
+            sender: Some("player".to_owned()),
+            rendered: rendered.to_owned(),
+            raw: format!(r#"{{"text":"{}"}}"#, rendered),
+        }
+    }
+
+    #[test]
+    fn latest_is_oldest_first() {
+        let history = History::from_connection(Connection::open_in_memory().unwrap()).unwrap();
+        let t0 = Utc::now();
+        history.record(&entry(t0, "first")).unwrap();
+        history.record(&entry(t0 + Duration::seconds(1), "second")).unwrap();
+        history.record(&entry(t0 + Duration::seconds(2), "third")).unwrap();
+
+        let rendered: Vec<&str> = history
+            .latest(2)
+            .unwrap()
+            .iter()
+            .map(|e| e.rendered.as_str())
+            .collect();
+        assert_eq!(rendered, vec!["second", "third"]);
+    }
+
+    #[test]
+    fn before_is_strict() {
+        let history = History::from_connection(Connection::open_in_memory().unwrap()).unwrap();
+        let t0 = Utc::now();
+        history.record(&entry(t0, "first")).unwrap();
+        history.record(&entry(t0 + Duration::seconds(1), "second")).unwrap();
+
+        // Strictly before the second message's own timestamp excludes it.
+        let rendered: Vec<&str> = history
+            .before(t0 + Duration::seconds(1), 10)
+            .unwrap()
+            .iter()
+            .map(|e| e.rendered.as_str())
+            .collect();
+        assert_eq!(rendered, vec!["first"]);
+    }
+}
@@ -0,0 +1,158 @@
+//! A minimal local IRC server, bridging a single channel to Minecraft chat.
+//!
+//! Speaks just enough of the protocol (CAP/NICK/USER/JOIN/PRIVMSG/PING) for
+//! an ordinary IRC client to connect, join `CHANNEL`, and read/write
+//! messages. This turns cobble into a reusable gateway: users keep their
+//! preferred IRC client for chat UI and logging instead of the bare console.
+
+use std::collections::HashMap;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::mpsc::Sender;
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+const CHANNEL: &str = "#minecraft";
+const SERVER_NAME: &str = "cobble";
+
+pub struct IrcGateway {
+    clients: Arc<Mutex<HashMap<u64, TcpStream>>>,
+}
+
+impl IrcGateway {
+    /// Binds `addr` and accepts IRC client connections in the background.
+    /// Text sent by clients as `PRIVMSG` to the channel is forwarded to
+    /// `outgoing`, the same channel `read_stdin` feeds in console mode.
+    pub fn listen(addr: &str, outgoing: Sender<String>) -> io::Result<IrcGateway> {
+        let listener = TcpListener::bind(addr)?;
+        let clients = Arc::new(Mutex::new(HashMap::new()));
+        let mut next_id = 0u64;
+
+        let accept_clients = Arc::clone(&clients);
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let stream = match stream {
+                    Ok(s) => s,
+                    Err(_) => continue,
+                };
+                let id = next_id;
+                next_id += 1;
+                let write_stream = match stream.try_clone() {
+                    Ok(s) => s,
+                    Err(_) => continue,
+                };
+                accept_clients.lock().unwrap().insert(id, write_stream);
+
+                let clients = Arc::clone(&accept_clients);
+                let outgoing = outgoing.clone();
+                thread::spawn(move || handle_client(id, stream, clients, outgoing));
+            }
+        });
+
+        Ok(IrcGateway { clients })
+    }
+
+    /// Renders a `PRIVMSG` from `sender` to the channel and pushes it to
+    /// every connected client, dropping any that have gone away.
+    pub fn broadcast(&self, sender: &str, text: &str) {
+        let sender = sanitize_sender(sender);
+        let text = sanitize_text(text);
+        let line = format!(
+            ":{}!{}@cobble PRIVMSG {} :{}\r\n",
+            sender, sender, CHANNEL, text
+        );
+        let mut clients = self.clients.lock().unwrap();
+        clients.retain(|_, stream| stream.write_all(line.as_bytes()).is_ok());
+    }
+}
+
+/// Strips CR/LF from `text` so embedded newlines (e.g. from Minecraft chat
+/// text, which `chat::render_plain` doesn't sanitize) can't forge a second
+/// protocol line once interpolated into a single `PRIVMSG`.
+fn sanitize_text(text: &str) -> String {
+    text.replace(['\r', '\n'], " ")
+}
+
+/// Strips all control characters and spaces from `sender`, which sits in the message
+/// prefix (`nick!user@host`) rather than behind a `:`, so even a stray
+/// space or CR/LF there could corrupt the line.
+fn sanitize_sender(sender: &str) -> String {
+    sender.chars().filter(|c| !c.is_control() && *c != ' ').collect()
+}
+
+fn handle_client(
+    id: u64,
+    stream: TcpStream,
+    clients: Arc<Mutex<HashMap<u64, TcpStream>>>,
+    outgoing: Sender<String>,
+) {
+    let mut writer = match stream.try_clone() {
+        Ok(s) => s,
+        Err(_) => return,
+    };
+    let mut reply = |line: &str| {
+        let _ = writer.write_all(line.as_bytes());
+        let _ = writer.write_all(b"\r\n");
+    };
+
+    let mut nick = String::from("*");
+    for line in BufReader::new(stream).lines() {
+        let line = match line {
+            Ok(l) => l,
+            Err(_) => break,
+        };
+        let line = line.trim_end();
+        if line.is_empty() {
+            continue;
+        }
+        let mut parts = line.splitn(2, ' ');
+        let command = parts.next().unwrap_or("").to_ascii_uppercase();
+        let rest = parts.next().unwrap_or("");
+
+        match command.as_str() {
+            "NICK" => nick = rest.trim().to_owned(),
+            "USER" => {
+                reply(&format!(":{} 001 {} :Welcome to cobble", SERVER_NAME, nick));
+                reply(&format!(":{} 376 {} :End of /MOTD command.", SERVER_NAME, nick));
+            }
+            "JOIN" => {
+                reply(&format!(":{}!{}@cobble JOIN {}", nick, nick, CHANNEL));
+                reply(&format!(
+                    ":{} 331 {} {} :No topic is set",
+                    SERVER_NAME, nick, CHANNEL
+                ));
+                reply(&format!(":{} 353 {} = {} :{}", SERVER_NAME, nick, CHANNEL, nick));
+                reply(&format!(
+                    ":{} 366 {} {} :End of /NAMES list.",
+                    SERVER_NAME, nick, CHANNEL
+                ));
+            }
+            "PRIVMSG" => {
+                let mut privmsg = rest.splitn(2, ' ');
+                let target = privmsg.next().unwrap_or("");
+                // Only bridge messages actually sent to the channel; a
+                // client-to-client or other-nick PRIVMSG isn't ours to relay.
+                if target == CHANNEL {
+                    if let Some(text) = privmsg.next().and_then(|p| p.strip_prefix(':')) {
+                        let _ = outgoing.send(text.to_owned());
+                    }
+                }
+            }
+            "PING" => reply(&format!("PONG :{}", rest)),
+            "CAP" => match rest.splitn(2, ' ').next().unwrap_or("").to_ascii_uppercase().as_str() {
+                // We don't support any capabilities, but we still have to
+                // answer LS/REQ or clients that negotiate (e.g. `CAP LS
+                // 302`) stall forever waiting for a reply before registering.
+                "LS" => reply(&format!(":{} CAP {} LS :", SERVER_NAME, nick)),
+                "REQ" => reply(&format!(":{} CAP {} NAK :", SERVER_NAME, nick)),
+                _ => (),
+            },
+            "QUIT" => break,
+            // Anything else we don't implement is silently ignored; clients
+            // fall back gracefully.
+            _ => (),
+        }
+    }
+
+    clients.lock().unwrap().remove(&id);
+}
@@ -12,6 +12,8 @@ use std::str::FromStr;
 use std::sync::mpsc::{channel, Sender};
 use std::{thread, time};
 
+use atty;
+use chrono::{DateTime, Utc};
 use ozelot::{self, clientbound::ClientboundPacket, mojang, serverbound};
 use rpassword;
 use serde::{Deserialize, Serialize};
@@ -19,22 +21,73 @@ use serde_json;
 use structopt::StructOpt;
 
 mod chat;
+mod discord_rpc;
+mod history;
+mod irc;
+mod msa;
+
+/// Where rendered chat lines (and `/history` replies) go: a bare console by
+/// default, or a bridged IRC channel when `--irc-listen` is given.
+enum Frontend {
+    Console,
+    Irc(irc::IrcGateway),
+}
+
+impl Frontend {
+    /// Prints a live chat component: colored for the console, but rendered
+    /// plain for the IRC bridge so the ANSI/OSC8 escapes and hover-event
+    /// footnotes used for console styling can't corrupt a PRIVMSG.
+    fn print(&self, sender: &str, component: &chat::Component) {
+        match self {
+            Frontend::Console => println!("{}", component),
+            Frontend::Irc(gateway) => gateway.broadcast(sender, &chat::render_plain(component)),
+        }
+    }
+
+    /// Prints text that's already plain, e.g. a stored history line.
+    fn print_plain(&self, sender: &str, text: &str) {
+        match self {
+            Frontend::Console => println!("{}", text),
+            Frontend::Irc(gateway) => gateway.broadcast(sender, text),
+        }
+    }
+}
 
 fn main() {
     let opt = Opt::from_args();
+    chat::set_color_enabled(opt.color || atty::is(atty::Stream::Stdout));
+    let history = history::History::open(&opt.history_db).expect("failed to open history database");
+    let mut rpc = opt.discord_rpc.as_ref().and_then(|app_id| {
+        discord_rpc::DiscordRpc::connect(app_id)
+            .map_err(|e| println!("Failed to connect to Discord RPC: {:?}", e))
+            .ok()
+    });
     let (mut client, mut username) = connect_to_server(
         &opt.account,
         &opt.server,
         opt.offline,
         opt.profile.as_ref().map(|x| &**x),
+        opt.auth,
     );
 
     println!("Connected!");
+    let mut connect_time = Utc::now().timestamp();
+    update_presence(&mut rpc, &opt.server, &username, "Connecting...", connect_time);
 
     let (tx, rx) = channel();
-    thread::spawn(move || {
-        read_stdin(tx);
-    });
+    let frontend = if let Some(addr) = &opt.irc_listen {
+        let gateway = irc::IrcGateway::listen(addr, tx).unwrap_or_else(|e| {
+            println!("Failed to start IRC gateway on {}: {}", addr, e);
+            exit(1);
+        });
+        println!("IRC gateway listening on {}", addr);
+        Frontend::Irc(gateway)
+    } else {
+        thread::spawn(move || read_stdin(tx));
+        Frontend::Console
+    };
+
+    let mut history_cursor = replay_history(&history, opt.history, &frontend).unwrap_or_else(Utc::now);
 
     'main: loop {
         let packets = match client.read() {
@@ -48,10 +101,14 @@ fn main() {
                     &opt.server,
                     opt.offline,
                     opt.profile.as_ref().map(|x| &**x),
+                    opt.auth,
                 );
                 client = x.0;
                 username = x.1;
                 println!("Connected!");
+                connect_time = Utc::now().timestamp();
+                update_presence(&mut rpc, &opt.server, &username, "Connecting...", connect_time);
+                history_cursor = replay_history(&history, opt.history, &frontend).unwrap_or_else(Utc::now);
                 continue 'main;
             }
             Err(e) => {
@@ -70,6 +127,7 @@ fn main() {
                 ClientboundPacket::JoinGame(_) => {
                     let settings = serverbound::ClientSettings::new(get_locale(), 2, 0, true, 0, 0);
                     client.send(settings).unwrap();
+                    update_presence(&mut rpc, &opt.server, &username, "Connected", connect_time);
                 }
                 ClientboundPacket::PlayDisconnect(ref p) if opt.reconnect => {
                     let reason: chat::Component = serde_json::from_str(p.get_reason()).unwrap();
@@ -81,10 +139,14 @@ fn main() {
                         &opt.server,
                         opt.offline,
                         opt.profile.as_ref().map(|x| &**x),
+                        opt.auth,
                     );
                     client = x.0;
                     username = x.1;
                     println!("Connected!");
+                    connect_time = Utc::now().timestamp();
+                    update_presence(&mut rpc, &opt.server, &username, "Connecting...", connect_time);
+                    history_cursor = replay_history(&history, opt.history, &frontend).unwrap_or_else(Utc::now);
                     continue 'main;
                 }
                 ClientboundPacket::PlayDisconnect(p) => {
@@ -95,23 +157,25 @@ fn main() {
                 }
                 ClientboundPacket::ChatMessage(p) => {
                     if let Ok(msg) = serde_json::from_str::<chat::Component>(p.get_chat()) {
-                        if let chat::Component::Translation(chat::TranslationComponent {
-                            translate,
-                            with,
-                            ..
-                        }) = &msg
-                        {
-                            if let [chat::Component::String(chat::StringComponent::Mixed {
-                                text: name,
-                                ..
-                            }), ..] = with.as_slice()
-                            {
-                                if translate == "chat.type.text" && name == &*username {
-                                    continue;
-                                }
+                        let sender = chat_sender(&msg);
+                        let entry = history::HistoryEntry {
+                            timestamp: Utc::now(),
+                            sender: sender.map(str::to_owned),
+                            // Stored plain: unlike a live console render, this
+                            // outlives the TTY/color state it was captured
+                            // under and may be replayed to any frontend.
+                            rendered: chat::render_plain(&msg),
+                            raw: p.get_chat().to_owned(),
+                        };
+                        if let Err(e) = history.record(&entry) {
+                            println!("Failed to store chat history: {}", e);
+                        }
+                        if let Some(name) = sender {
+                            if name == &*username {
+                                continue;
                             }
                         }
-                        println!("{}", msg);
+                        frontend.print(sender.unwrap_or("server"), &msg);
                     } else {
                         println!("Failed to parse message: {}", p.get_chat());
                     }
@@ -121,12 +185,90 @@ fn main() {
         }
 
         if let Ok(msg) = rx.recv_timeout(timeout) {
-            let chat = serverbound::ChatMessage::new(msg);
-            client.send(chat).unwrap();
+            if let Some(count) = msg.trim().strip_prefix("/history") {
+                let count: usize = count.trim().parse().unwrap_or(10);
+                match history.before(history_cursor, count) {
+                    Ok(entries) => {
+                        if let Some(first) = entries.first() {
+                            history_cursor = first.timestamp;
+                        }
+                        print_history(&entries, &frontend);
+                    }
+                    Err(e) => println!("Failed to read history: {}", e),
+                }
+            } else {
+                let chat = serverbound::ChatMessage::new(msg);
+                client.send(chat).unwrap();
+            }
+        }
+    }
+}
+
+/// Extracts the sending player's name from a `chat.type.text`-translated
+/// chat message, the same shape used for ordinary player chat.
+fn chat_sender(msg: &chat::Component) -> Option<&str> {
+    if let chat::Component::Translation(chat::TranslationComponent { translate, with, .. }) = msg {
+        if translate == "chat.type.text" {
+            if let [chat::Component::String(chat::StringComponent::Mixed { text: name, .. }), ..] =
+                with.as_slice()
+            {
+                return Some(name);
+            }
+        }
+    }
+    None
+}
+
+/// Publishes `state` (e.g. "Connecting..."/"Connected") to Discord Rich
+/// Presence, if `--discord-rpc` enabled it, showing the server and account
+/// being played and how long the connection has been up.
+fn update_presence(
+    rpc: &mut Option<discord_rpc::DiscordRpc>,
+    server: &ServerAddress,
+    username: &str,
+    state: &str,
+    connect_time: i64,
+) {
+    if let Some(rpc) = rpc {
+        let details = format!("{} as {}", server, username);
+        if let Err(e) = rpc.set_activity(state, &details, connect_time) {
+            println!("Failed to update Discord presence: {:?}", e);
         }
     }
 }
 
+/// Prints the last `count` stored messages (if any) and returns the
+/// timestamp of the oldest one printed, for use as the next page's cursor.
+fn replay_history(
+    history: &history::History,
+    count: Option<usize>,
+    frontend: &Frontend,
+) -> Option<DateTime<Utc>> {
+    let count = count?;
+    match history.latest(count) {
+        Ok(entries) => {
+            let cursor = entries.first().map(|e| e.timestamp);
+            print_history(&entries, frontend);
+            cursor
+        }
+        Err(e) => {
+            println!("Failed to read history: {}", e);
+            None
+        }
+    }
+}
+
+fn print_history(entries: &[history::HistoryEntry], frontend: &Frontend) {
+    for entry in entries {
+        let line = format!(
+            "[{}] {}",
+            entry.timestamp.format("%Y-%m-%d %H:%M:%S"),
+            entry.rendered
+        );
+        frontend.print_plain(entry.sender.as_deref().unwrap_or("history"), &line);
+    }
+}
+
 #[derive(StructOpt, Debug)]
 struct Opt {
     /// Mojang account
@@ -143,6 +285,45 @@ struct Opt {
     /// Enable auto-Reconnect
     #[structopt(short = "r", long)]
     reconnect: bool,
+    /// Force ANSI colored/styled output (default: auto-detect based on whether
+    /// stdout is a terminal)
+    #[structopt(long)]
+    color: bool,
+    /// Authentication method: "mojang" (legacy email+password) or "msa"
+    /// (Microsoft/Xbox Live, required for migrated accounts)
+    #[structopt(long, default_value = "mojang")]
+    auth: AuthMethod,
+    /// Path to the SQLite chat history database
+    #[structopt(long, default_value = "cobble_history.db")]
+    history_db: String,
+    /// Replay the last N stored messages on connect and after each reconnect
+    #[structopt(long)]
+    history: Option<usize>,
+    /// Bridge chat to a local IRC server listening on this address (e.g.
+    /// "127.0.0.1:6667") instead of reading/writing the console directly
+    #[structopt(long)]
+    irc_listen: Option<String>,
+    /// Show a Discord Rich Presence activity for the given application id
+    #[structopt(long)]
+    discord_rpc: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum AuthMethod {
+    Mojang,
+    Msa,
+}
+
+impl FromStr for AuthMethod {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "mojang" => Ok(AuthMethod::Mojang),
+            "msa" => Ok(AuthMethod::Msa),
+            _ => Err(format!("invalid auth method '{}', expected mojang or msa", s)),
+        }
+    }
 }
 
 #[derive(Debug, PartialEq)]
@@ -182,9 +363,10 @@ fn connect_to_server<'a>(
     server: &ServerAddress,
     offline_mode: bool,
     profile: Option<&str>,
+    auth_method: AuthMethod,
 ) -> (ozelot::Client, Cow<'a, str>) {
     if !offline_mode {
-        let auth = authenticate(account, profile);
+        let auth = authenticate(account, profile, auth_method);
         println!("Authentication successful!, connecting to server...");
         match ozelot::Client::connect_authenticated(&server.host, server.port, &auth) {
             Ok(x) => (x, Cow::Owned(auth.selectedProfile.name)),
@@ -205,7 +387,18 @@ fn connect_to_server<'a>(
     }
 }
 
-fn authenticate(account: &str, profile: Option<&str>) -> mojang::AuthenticationResponse {
+fn authenticate(
+    account: &str,
+    profile: Option<&str>,
+    auth_method: AuthMethod,
+) -> mojang::AuthenticationResponse {
+    match auth_method {
+        AuthMethod::Mojang => authenticate_mojang(account, profile),
+        AuthMethod::Msa => authenticate_msa(profile),
+    }
+}
+
+fn authenticate_mojang(account: &str, profile: Option<&str>) -> mojang::AuthenticationResponse {
     let ask_passwd = || {
         let password = rpassword::prompt_password_stdout("Enter password: ").unwrap();
         mojang::Authenticate::new(account.to_owned(), password)
@@ -252,6 +445,60 @@ fn authenticate(account: &str, profile: Option<&str>) -> mojang::AuthenticationR
     }
 }
 
+fn authenticate_msa(profile: Option<&str>) -> mojang::AuthenticationResponse {
+    let session_to_profile = |session: msa::MsaSession| AuthProfile {
+        access_token: session.access_token,
+        client_token: None,
+        available_profiles: None,
+        selected_profile: NameUUID {
+            id: session.uuid,
+            name: session.name,
+            legacy: false,
+            demo: false,
+        },
+        msa_refresh_token: Some(session.refresh_token),
+    };
+    let login = || {
+        let session = msa::login().unwrap_or_else(|e| {
+            println!("Microsoft authentication failed: {:?}", e);
+            exit(1);
+        });
+        session_to_profile(session)
+    };
+    if let Some(config_path) = profile {
+        let config_path = Path::new(&config_path);
+        let config = if config_path.exists() {
+            println!("Reading profile...");
+            let config = read_to_string(&config_path).expect("failed to read profile");
+            let config: AuthProfile = serde_json::from_str(&config).expect("");
+            match &config.msa_refresh_token {
+                Some(refresh_token) => match msa::reauthenticate(refresh_token) {
+                    Ok(session) => {
+                        println!("Valid profile!");
+                        session_to_profile(session)
+                    }
+                    Err(e) => {
+                        println!("Failed to refresh profile ({:?}), please re-login.", e);
+                        login()
+                    }
+                },
+                None => {
+                    println!("Profile is not a Microsoft account, please re-login.");
+                    login()
+                }
+            }
+        } else {
+            println!("Profile doesn't exists, please login.");
+            login()
+        };
+        let file = File::create(&config_path).expect("");
+        serde_json::to_writer_pretty(&file, &config).expect("");
+        config.into()
+    } else {
+        login().into()
+    }
+}
+
 fn get_locale() -> String {
     match env::var("LANG") {
         Ok(ref lang) if lang != "C" => lang.split('.').next().unwrap().to_owned(),
@@ -273,6 +520,10 @@ struct AuthProfile {
     client_token: Option<String>,
     available_profiles: Option<Vec<NameUUID>>,
     selected_profile: NameUUID,
+    /// Present for profiles created via `--auth msa`; used to silently
+    /// refresh the Microsoft session instead of re-prompting for login.
+    #[serde(default)]
+    msa_refresh_token: Option<String>,
 }
 
 impl From<AuthProfile> for mojang::AuthenticationResponse {
@@ -297,6 +548,7 @@ impl From<mojang::AuthenticationResponse> for AuthProfile {
                 .availableProfiles
                 .map(|x| x.into_iter().map(Into::into).collect()),
             selected_profile: profile.selectedProfile.into(),
+            msa_refresh_token: None,
         }
     }
 }
@@ -351,6 +603,13 @@ mod test {
         assert_eq!(format!("{}", r), "127.0.0.1:25566");
     }
 
+    #[test]
+    fn auth_method_from_str() {
+        assert_eq!(AuthMethod::from_str("mojang").unwrap(), AuthMethod::Mojang);
+        assert_eq!(AuthMethod::from_str("msa").unwrap(), AuthMethod::Msa);
+        assert!(AuthMethod::from_str("xbox").is_err());
+    }
+
     #[test]
     fn server_address_default_port() {
         let r = ServerAddress::from_str("127.0.0.1").unwrap();
@@ -0,0 +1,287 @@
+//! Microsoft/Xbox Live authentication for migrated (modern) Minecraft accounts.
+//!
+//! Implements the device-code flow described at
+//! https://wiki.vg/Microsoft_Authentication_Scheme: an MS OAuth token is
+//! exchanged for an Xbox Live (XBL) token, then an XSTS token, then finally a
+//! Minecraft services token that can be used to fetch the game profile.
+
+use std::{thread, time};
+
+use serde::Deserialize;
+use serde_json::json;
+
+/// Azure AD application id for this client, registered as a "public client"
+/// with the device code flow enabled, matching the `login.microsoftonline.com`
+/// endpoints below. The legacy `00000000402b5328` id documented in older
+/// revisions of https://wiki.vg/Microsoft_Authentication_Scheme only works
+/// against the old `login.live.com/oauth20_*` endpoints, not these v2.0 ones.
+const CLIENT_ID: &str = "54fd49e4-2103-4044-9603-2b028c814ec3";
+
+const DEVICE_CODE_URL: &str = "https://login.microsoftonline.com/consumers/oauth2/v2.0/devicecode";
+const TOKEN_URL: &str = "https://login.microsoftonline.com/consumers/oauth2/v2.0/token";
+const XBL_AUTH_URL: &str = "https://user.auth.xboxlive.com/user/authenticate";
+const XSTS_AUTH_URL: &str = "https://xsts.auth.xboxlive.com/xsts/authorize";
+const MC_LOGIN_URL: &str = "https://api.minecraftservices.com/authentication/login_with_xbox";
+const MC_PROFILE_URL: &str = "https://api.minecraftservices.com/minecraft/profile";
+
+#[derive(Debug)]
+pub enum MsaError {
+    Request(reqwest::Error),
+    /// The device code's `expires_in` deadline passed before the user
+    /// finished signing in.
+    DeviceCodeExpired,
+    /// A terminal error from the token endpoint other than
+    /// `authorization_pending`/`slow_down` (e.g. `authorization_declined`,
+    /// `expired_token`, `bad_verification_code`), carrying Microsoft's
+    /// `error` string verbatim.
+    Token(String),
+    /// Xbox Live (XBL or XSTS) rejected the token, e.g. no Xbox profile on
+    /// this Microsoft account, or a child account outside a Family group.
+    /// Carries a human-readable reason derived from Xbox Live's `XErr` code.
+    Xbox(String),
+    /// The Minecraft login or profile endpoint returned a non-success
+    /// status, e.g. because the account doesn't own the game.
+    Minecraft(String),
+    NoXboxProfile,
+}
+
+impl From<reqwest::Error> for MsaError {
+    fn from(e: reqwest::Error) -> MsaError {
+        MsaError::Request(e)
+    }
+}
+
+/// The outcome of a successful MSA login: enough to play, plus the refresh
+/// token needed to silently re-authenticate on the next launch.
+#[derive(Debug, Clone)]
+pub struct MsaSession {
+    pub access_token: String,
+    pub refresh_token: String,
+    pub uuid: String,
+    pub name: String,
+}
+
+#[derive(Deserialize)]
+struct DeviceCodeResponse {
+    device_code: String,
+    user_code: String,
+    verification_uri: String,
+    interval: u64,
+    expires_in: u64,
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    refresh_token: String,
+}
+
+#[derive(Deserialize)]
+struct TokenErrorResponse {
+    error: String,
+}
+
+#[derive(Deserialize)]
+struct XblResponse {
+    #[serde(rename = "Token")]
+    token: String,
+    #[serde(rename = "DisplayClaims")]
+    display_claims: XblDisplayClaims,
+}
+
+#[derive(Deserialize)]
+struct XblDisplayClaims {
+    xui: Vec<XblUserHash>,
+}
+
+#[derive(Deserialize)]
+struct XblUserHash {
+    uhs: String,
+}
+
+/// Error body returned by the XBL/XSTS endpoints on a non-success status,
+/// documented at https://wiki.vg/Microsoft_Authentication_Scheme#Authenticate_with_XSTS.
+#[derive(Deserialize)]
+struct XblErrorResponse {
+    #[serde(rename = "XErr")]
+    x_err: u64,
+}
+
+fn xbox_error_reason(x_err: u64) -> &'static str {
+    match x_err {
+        2148916233 => "this Microsoft account has no Xbox profile; create one at xbox.com first",
+        2148916235 => "Xbox Live is not available for this account's country/region",
+        2148916236 | 2148916237 => "this account needs adult verification on xbox.com",
+        2148916238 => "this is a child account that must be added to a Family",
+        _ => "Xbox Live rejected the token",
+    }
+}
+
+#[derive(Deserialize)]
+struct McLoginResponse {
+    access_token: String,
+}
+
+#[derive(Deserialize)]
+struct McProfileResponse {
+    id: String,
+    name: String,
+}
+
+/// Runs the full device-code login: prints the verification URL and code for
+/// the user to open in a browser, then polls until they complete it.
+pub fn login() -> Result<MsaSession, MsaError> {
+    let client = reqwest::blocking::Client::new();
+
+    let device_code: DeviceCodeResponse = client
+        .post(DEVICE_CODE_URL)
+        .form(&[
+            ("client_id", CLIENT_ID),
+            ("scope", "XboxLive.signin offline_access"),
+        ])
+        .send()?
+        .json()?;
+
+    println!(
+        "To sign in, open {} and enter the code: {}",
+        device_code.verification_uri, device_code.user_code
+    );
+
+    let deadline = time::Instant::now() + time::Duration::from_secs(device_code.expires_in);
+    let mut interval = time::Duration::from_secs(device_code.interval);
+    let ms_token = loop {
+        if time::Instant::now() >= deadline {
+            return Err(MsaError::DeviceCodeExpired);
+        }
+        thread::sleep(interval);
+
+        let resp = client
+            .post(TOKEN_URL)
+            .form(&[
+                ("client_id", CLIENT_ID),
+                (
+                    "grant_type",
+                    "urn:ietf:params:oauth:grant-type:device_code",
+                ),
+                ("device_code", &device_code.device_code),
+            ])
+            .send()?;
+        if resp.status().is_success() {
+            break resp.json::<TokenResponse>()?;
+        }
+        let err: TokenErrorResponse = resp.json()?;
+        match err.error.as_str() {
+            "authorization_pending" => {}
+            // Per RFC 8628: back off instead of polling at the same rate.
+            "slow_down" => interval += time::Duration::from_secs(5),
+            _ => return Err(MsaError::Token(err.error)),
+        }
+    };
+
+    refresh(&client, &ms_token.access_token, ms_token.refresh_token)
+}
+
+/// Re-authenticates using a previously stored MS refresh token, without
+/// prompting the user.
+pub fn reauthenticate(refresh_token: &str) -> Result<MsaSession, MsaError> {
+    let client = reqwest::blocking::Client::new();
+    let ms_token: TokenResponse = client
+        .post(TOKEN_URL)
+        .form(&[
+            ("client_id", CLIENT_ID),
+            ("grant_type", "refresh_token"),
+            ("refresh_token", refresh_token),
+        ])
+        .send()?
+        .json()?;
+
+    refresh(&client, &ms_token.access_token, ms_token.refresh_token)
+}
+
+/// The XBL -> XSTS -> Minecraft -> profile leg shared by both the initial
+/// device-code login and silent refreshes.
+fn refresh(
+    client: &reqwest::blocking::Client,
+    ms_access_token: &str,
+    ms_refresh_token: String,
+) -> Result<MsaSession, MsaError> {
+    let xbl_resp = client
+        .post(XBL_AUTH_URL)
+        .json(&json!({
+            "Properties": {
+                "AuthMethod": "RPS",
+                "SiteName": "user.auth.xboxlive.com",
+                "RpsTicket": format!("d={}", ms_access_token),
+            },
+            "RelyingParty": "http://auth.xboxlive.com",
+            "TokenType": "JWT",
+        }))
+        .send()?;
+    let xbl = xbox_response(xbl_resp)?;
+    let uhs = xbl
+        .display_claims
+        .xui
+        .into_iter()
+        .next()
+        .ok_or(MsaError::NoXboxProfile)?
+        .uhs;
+
+    let xsts_resp = client
+        .post(XSTS_AUTH_URL)
+        .json(&json!({
+            "Properties": {
+                "SandboxId": "RETAIL",
+                "UserTokens": [xbl.token],
+            },
+            "RelyingParty": "rp://api.minecraftservices.com/",
+            "TokenType": "JWT",
+        }))
+        .send()?;
+    let xsts = xbox_response(xsts_resp)?;
+
+    let mc_resp = client
+        .post(MC_LOGIN_URL)
+        .json(&json!({
+            "identityToken": format!("XBL3.0 x={};{}", uhs, xsts.token),
+        }))
+        .send()?;
+    if !mc_resp.status().is_success() {
+        return Err(MsaError::Minecraft(format!(
+            "login failed with status {}",
+            mc_resp.status()
+        )));
+    }
+    let mc: McLoginResponse = mc_resp.json()?;
+
+    let profile_resp = client.get(MC_PROFILE_URL).bearer_auth(&mc.access_token).send()?;
+    if !profile_resp.status().is_success() {
+        return Err(MsaError::Minecraft(format!(
+            "profile lookup failed with status {} (account may not own Minecraft)",
+            profile_resp.status()
+        )));
+    }
+    let profile: McProfileResponse = profile_resp.json()?;
+
+    Ok(MsaSession {
+        access_token: mc.access_token,
+        refresh_token: ms_refresh_token,
+        uuid: profile.id,
+        name: profile.name,
+    })
+}
+
+/// Parses an XBL/XSTS response, mapping a non-success status to a
+/// `MsaError::Xbox` with a reason derived from the `XErr` error body
+/// instead of letting a bare JSON-decode error surface from `.json()`.
+fn xbox_response(resp: reqwest::blocking::Response) -> Result<XblResponse, MsaError> {
+    if resp.status().is_success() {
+        Ok(resp.json()?)
+    } else {
+        let err: XblErrorResponse = resp.json()?;
+        Err(MsaError::Xbox(format!(
+            "{} ({})",
+            xbox_error_reason(err.x_err),
+            err.x_err
+        )))
+    }
+}